@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use image::{ColorType, ImageEncoder, ImageError, ImageResult};
+use thiserror::Error;
+
+use crate::parser::hash_pixel;
+use crate::{encoding_error, Channels, Colorspace, Pixel};
+
+pub struct QoiEncoder<W> {
+    writer: W,
+}
+
+impl<W: Write> QoiEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        QoiEncoder { writer }
+    }
+}
+
+impl<W: Write> ImageEncoder for QoiEncoder<W> {
+    fn write_image(
+        mut self,
+        buf: &[u8],
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+    ) -> ImageResult<()> {
+        let channels = match color_type {
+            ColorType::Rgb8 => Channels::Rgb,
+            ColorType::Rgba8 => Channels::Rgba,
+            _ => return Err(encoding_error(EncoderError::UnsupportedColorType)),
+        };
+
+        if buf.len() != (width as usize) * (height as usize) * (channels as usize) {
+            return Err(encoding_error(EncoderError::BufferSizeMismatch));
+        }
+
+        self.writer
+            .write_all(b"qoif")
+            .map_err(ImageError::IoError)?;
+        self.writer
+            .write_all(&width.to_be_bytes())
+            .map_err(ImageError::IoError)?;
+        self.writer
+            .write_all(&height.to_be_bytes())
+            .map_err(ImageError::IoError)?;
+        self.writer
+            .write_all(&[channels as u8, Colorspace::Srgb as u8])
+            .map_err(ImageError::IoError)?;
+
+        encode_image_content(&mut self.writer, buf, channels).map_err(ImageError::IoError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct EncoderState {
+    prev: Pixel,
+    seen: [Pixel; 64],
+}
+
+fn encode_image_content<W: Write>(
+    writer: &mut W,
+    pixels: &[u8],
+    channels: Channels,
+) -> std::io::Result<()> {
+    let channel_count = channels as usize;
+
+    let mut state = EncoderState {
+        prev: Pixel {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 255,
+        },
+        seen: [Pixel::default(); 64],
+    };
+    let mut run = 0u8;
+
+    for chunk in pixels.chunks_exact(channel_count) {
+        let pixel = Pixel {
+            red: chunk[0],
+            green: chunk[1],
+            blue: chunk[2],
+            alpha: if channel_count == 4 {
+                chunk[3]
+            } else {
+                state.prev.alpha
+            },
+        };
+
+        if pixel == state.prev {
+            run += 1;
+            if run == 62 {
+                write_run(writer, run)?;
+                run = 0;
+            }
+            continue;
+        }
+
+        if run > 0 {
+            write_run(writer, run)?;
+            run = 0;
+        }
+
+        write_pixel(writer, pixel, &mut state)?;
+    }
+
+    if run > 0 {
+        write_run(writer, run)?;
+    }
+
+    writer.write_all(&[0, 0, 0, 0, 0, 0, 0, 1])
+}
+
+fn write_run<W: Write>(writer: &mut W, run: u8) -> std::io::Result<()> {
+    writer.write_all(&[0b11000000 | (run - 1)])
+}
+
+fn write_pixel<W: Write>(
+    writer: &mut W,
+    pixel: Pixel,
+    state: &mut EncoderState,
+) -> std::io::Result<()> {
+    let index = hash_pixel(pixel);
+    if state.seen[index] == pixel {
+        writer.write_all(&[index as u8])?;
+        state.prev = pixel;
+        return Ok(());
+    }
+    state.seen[index] = pixel;
+
+    if pixel.alpha != state.prev.alpha {
+        writer.write_all(&[0b11111111, pixel.red, pixel.green, pixel.blue, pixel.alpha])?;
+        state.prev = pixel;
+        return Ok(());
+    }
+
+    let dr = pixel.red.wrapping_sub(state.prev.red) as i8;
+    let dg = pixel.green.wrapping_sub(state.prev.green) as i8;
+    let db = pixel.blue.wrapping_sub(state.prev.blue) as i8;
+
+    if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+        let byte =
+            0b01000000 | (((dr + 2) as u8) << 4) | (((dg + 2) as u8) << 2) | (db + 2) as u8;
+        writer.write_all(&[byte])?;
+        state.prev = pixel;
+        return Ok(());
+    }
+
+    let dr_dg = dr.wrapping_sub(dg);
+    let db_dg = db.wrapping_sub(dg);
+
+    if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+        let byte1 = 0b10000000 | (dg + 32) as u8;
+        let byte2 = (((dr_dg + 8) as u8) << 4) | (db_dg + 8) as u8;
+        writer.write_all(&[byte1, byte2])?;
+        state.prev = pixel;
+        return Ok(());
+    }
+
+    writer.write_all(&[0b11111110, pixel.red, pixel.green, pixel.blue])?;
+    state.prev = pixel;
+    Ok(())
+}
+
+#[derive(Error, Debug, Clone, Copy)]
+pub enum EncoderError {
+    UnsupportedColorType,
+    BufferSizeMismatch,
+}
+
+impl Display for EncoderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
@@ -1,24 +1,38 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
 use std::io::{Cursor, Read};
 
+#[cfg(feature = "std")]
 use image::{
-    error::{DecodingError, ImageFormatHint},
+    error::{DecodingError, EncodingError, ImageFormatHint},
     ColorType, ImageDecoder, ImageError, ImageFormat, ImageResult,
 };
 
 const QOIF_HEADER_LENGTH: usize = 14;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
 
+#[cfg(feature = "std")]
+mod encoder;
 mod parser;
+#[cfg(feature = "std")]
+mod streaming;
 
+#[cfg(feature = "std")]
+pub use encoder::*;
 pub use parser::*;
+#[cfg(feature = "std")]
+pub use streaming::*;
 
+#[cfg(feature = "std")]
 pub struct QoiDecoder<R> {
     reader: R,
     header: Header,
 }
 
+#[cfg(feature = "std")]
 impl<R: Read> QoiDecoder<R> {
     pub fn new(mut reader: R) -> ImageResult<Self> {
         let mut header_buf = [0u8; QOIF_HEADER_LENGTH];
@@ -46,6 +60,7 @@ struct Pixel {
     alpha: u8,
 }
 
+#[cfg(feature = "std")]
 impl<R: Read> ImageDecoder<'_> for QoiDecoder<R> {
     type Reader = Cursor<Vec<u8>>;
 
@@ -70,6 +85,7 @@ impl<R: Read> ImageDecoder<'_> for QoiDecoder<R> {
     }
 }
 
+#[cfg(feature = "std")]
 fn decoding_error(err: DecoderError) -> ImageError {
     ImageError::Decoding(DecodingError::new(
         ImageFormatHint::Exact(ImageFormat::Qoi),
@@ -77,6 +93,14 @@ fn decoding_error(err: DecoderError) -> ImageError {
     ))
 }
 
+#[cfg(feature = "std")]
+fn encoding_error(err: EncoderError) -> ImageError {
+    ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Qoi),
+        err,
+    ))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
     width: u32,
@@ -85,6 +109,17 @@ pub struct Header {
     colorspace: Colorspace,
 }
 
+impl Header {
+    /// The number of bytes a fully decoded image occupies: `width * height * channels`.
+    pub fn required_bytes(&self) -> usize {
+        let channel_count = match self.channels {
+            Channels::Rgb => 3,
+            Channels::Rgba => 4,
+        };
+        self.width as usize * self.height as usize * channel_count
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 enum Channels {
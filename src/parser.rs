@@ -1,33 +1,54 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 use crate::{Channels, Colorspace, Header, Pixel};
 
 const QOIF_MAGIC: &[u8] = b"qoif";
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
 
 pub fn parse_image_header(header_bytes: &[u8]) -> Result<Header, DecoderError> {
-    let bytes_left = tag(QOIF_MAGIC, header_bytes).map_err(|_| DecoderError::InvalidMagic)?;
+    let offset_of = |rest: &[u8]| header_bytes.len() - rest.len();
 
-    let (width, bytes_left) = be_u32(bytes_left).map_err(|_| DecoderError::TooShortHeader)?;
+    let bytes_left = tag(QOIF_MAGIC, header_bytes).map_err(|_| DecoderError::InvalidMagic {
+        offset: offset_of(header_bytes),
+    })?;
 
-    let (height, bytes_left) = be_u32(bytes_left).map_err(|_| DecoderError::TooShortHeader)?;
+    let (width, bytes_left) = be_u32(bytes_left).map_err(|_| DecoderError::TooShortHeader {
+        offset: offset_of(bytes_left),
+    })?;
 
-    let (channels, bytes_left) = u8(bytes_left).map_err(|_| DecoderError::TooShortHeader)?;
+    let (height, bytes_left) = be_u32(bytes_left).map_err(|_| DecoderError::TooShortHeader {
+        offset: offset_of(bytes_left),
+    })?;
+
+    let (channels, rest) = u8(bytes_left).map_err(|_| DecoderError::TooShortHeader {
+        offset: offset_of(bytes_left),
+    })?;
     let channels = match channels {
         3 => Channels::Rgb,
         4 => Channels::Rgba,
         _ => {
-            return Err(DecoderError::InvalidChannels);
+            return Err(DecoderError::InvalidChannels {
+                offset: offset_of(bytes_left),
+            });
         }
     };
+    let bytes_left = rest;
 
-    let (colorspace, _) = u8(bytes_left).map_err(|_| DecoderError::TooShortHeader)?;
+    let (colorspace, _) = u8(bytes_left).map_err(|_| DecoderError::TooShortHeader {
+        offset: offset_of(bytes_left),
+    })?;
 
     let colorspace = match colorspace {
         0 => Colorspace::Srgb,
         1 => Colorspace::Linear,
-        _ => return Err(DecoderError::InvalidColorspace),
+        _ => {
+            return Err(DecoderError::InvalidColorspace {
+                offset: offset_of(bytes_left),
+            })
+        }
     };
 
     Ok(Header {
@@ -38,15 +59,32 @@ pub fn parse_image_header(header_bytes: &[u8]) -> Result<Header, DecoderError> {
     })
 }
 
+/// Std-only convenience wrapper over [`decode_into`] that allocates the output `Vec` itself,
+/// so the hot op-dispatch loop and the end-marker check only exist in one place.
+#[cfg(feature = "std")]
 pub fn parse_image_content(content_bytes: &[u8], header: Header) -> Result<Vec<u8>, DecoderError> {
-    let result_len = match header.channels {
-        Channels::Rgba => header.height * header.width * 4,
-        Channels::Rgb => header.height * header.width * 3,
-    } as usize;
+    let mut pixels = vec![0u8; header.required_bytes()];
+    decode_into(content_bytes, header, &mut pixels)?;
+    Ok(pixels)
+}
 
-    let mut pixels = Vec::with_capacity(result_len);
+/// Decodes `content_bytes` into the caller-supplied `out` buffer instead of allocating a `Vec`,
+/// so it can run under `#![no_std]` with only `core`. Validates the trailing 8-byte end marker
+/// the same way [`parse_image_content`] does. Returns the number of bytes written.
+pub fn decode_into(content_bytes: &[u8], header: Header, out: &mut [u8]) -> Result<usize, DecoderError> {
+    let required = header.required_bytes();
+    if out.len() < required {
+        return Err(DecoderError::BufferTooSmall {
+            required,
+            available: out.len(),
+        });
+    }
 
-    let mut bytes_left = content_bytes;
+    let channel_count = match header.channels {
+        Channels::Rgb => 3,
+        Channels::Rgba => 4,
+    };
+    let is_alpha = matches!(header.channels, Channels::Rgba);
 
     let mut state = ParserState {
         prev: Pixel {
@@ -56,48 +94,147 @@ pub fn parse_image_content(content_bytes: &[u8], header: Header) -> Result<Vec<u
             alpha: 255,
         },
         seen: [Default::default(); 64],
-        is_alpha: match header.channels {
-            Channels::Rgba => true,
-            Channels::Rgb => false,
-        },
+        is_alpha,
     };
 
-    while !bytes_left.is_empty() {
-        'pixel_block: {
-            for parser in [
-                qoi_op_rgb,
-                qoi_op_rgba,
-                qoi_op_end,
-                qoi_op_index,
-                qoi_op_diff,
-                qoi_op_luma,
-                qoi_op_run,
-            ] {
-                match parser(bytes_left, &mut pixels, &mut state) {
-                    Err(ParserError::Recoverable) => {}
-                    Err(ParserError::Invalid) => return Err(DecoderError::TooFewPixels),
-                    Ok(new_input) => {
-                        bytes_left = new_input;
-                        break 'pixel_block;
-                    }
-                }
+    let mut input = content_bytes;
+    let mut written = 0usize;
+
+    // Reading the tag byte once and matching on it avoids paying for up to six failed
+    // trial parses (and their redundant bounds checks) per pixel.
+    while written < required {
+        let offset = content_bytes.len() - input.len();
+        let (tag_byte, rest) = u8(input).map_err(|_| DecoderError::TooFewPixels {
+            offset,
+            decoded_len: written,
+        })?;
+
+        let (pixel, run) = match tag_byte {
+            0b11111110 => {
+                let (channel_bytes, rest2) = take(rest, 3).map_err(|_| DecoderError::TooFewPixels {
+                    offset,
+                    decoded_len: written,
+                })?;
+                input = rest2;
+                (
+                    Pixel {
+                        red: channel_bytes[0],
+                        green: channel_bytes[1],
+                        blue: channel_bytes[2],
+                        alpha: state.prev.alpha,
+                    },
+                    1,
+                )
+            }
+            0b11111111 => {
+                let (channel_bytes, rest2) = take(rest, 4).map_err(|_| DecoderError::TooFewPixels {
+                    offset,
+                    decoded_len: written,
+                })?;
+                input = rest2;
+                (
+                    Pixel {
+                        red: channel_bytes[0],
+                        green: channel_bytes[1],
+                        blue: channel_bytes[2],
+                        alpha: channel_bytes[3],
+                    },
+                    1,
+                )
+            }
+            byte if byte >> 6 == 0b00 => {
+                input = rest;
+                (state.seen[byte as usize], 1)
             }
-            return Err(DecoderError::InvalidPixel);
+            byte if byte >> 6 == 0b01 => {
+                input = rest;
+                let dr = ((byte >> 4) & 0b11).wrapping_sub(2);
+                let dg = ((byte >> 2) & 0b11).wrapping_sub(2);
+                let db = (byte & 0b11).wrapping_sub(2);
+                (
+                    Pixel {
+                        red: state.prev.red.wrapping_add(dr),
+                        green: state.prev.green.wrapping_add(dg),
+                        blue: state.prev.blue.wrapping_add(db),
+                        alpha: state.prev.alpha,
+                    },
+                    1,
+                )
+            }
+            byte if byte >> 6 == 0b10 => {
+                let (byte2, rest2) = u8(rest).map_err(|_| DecoderError::TooFewPixels {
+                    offset,
+                    decoded_len: written,
+                })?;
+                input = rest2;
+                let dg = (byte & 0b0011_1111).wrapping_sub(32);
+                let dr = dg.wrapping_add((byte2 >> 4).wrapping_sub(8));
+                let db = dg.wrapping_add((byte2 & 0b1111).wrapping_sub(8));
+                (
+                    Pixel {
+                        red: state.prev.red.wrapping_add(dr),
+                        green: state.prev.green.wrapping_add(dg),
+                        blue: state.prev.blue.wrapping_add(db),
+                        alpha: state.prev.alpha,
+                    },
+                    1,
+                )
+            }
+            byte => {
+                input = rest;
+                (state.prev, (byte & 0b0011_1111).wrapping_add(1) as usize)
+            }
+        };
+
+        for _ in 0..run {
+            if written + channel_count > required {
+                return Err(DecoderError::TooManyPixels {
+                    offset: content_bytes.len() - input.len(),
+                    pixel_index: required / channel_count,
+                    decoded_len: written,
+                });
+            }
+            out[written] = pixel.red;
+            out[written + 1] = pixel.green;
+            out[written + 2] = pixel.blue;
+            if is_alpha {
+                out[written + 3] = pixel.alpha;
+            }
+            written += channel_count;
         }
+
+        state.prev = pixel;
+        state.seen[hash_pixel(pixel)] = pixel;
     }
 
-    if pixels.len() < result_len {
-        return Err(DecoderError::TooFewPixels);
+    // All declared pixels are decoded; only the 8-byte end marker should remain.
+    if input.len() < END_MARKER.len() {
+        return Err(DecoderError::TooFewPixels {
+            offset: content_bytes.len() - input.len(),
+            decoded_len: written,
+        });
     }
 
-    if pixels.len() > result_len {
-        return Err(DecoderError::TooManyPixels);
+    if input != END_MARKER.as_slice() {
+        return Err(DecoderError::InvalidPixel {
+            offset: content_bytes.len() - input.len(),
+            decoded_len: written,
+        });
     }
 
-    Ok(pixels)
+    Ok(written)
 }
 
-fn qoi_op_rgb<'a>(
+fn take(input: &[u8], n: usize) -> Result<(&[u8], &[u8]), ParserError> {
+    if input.len() < n {
+        Err(ParserError::Invalid)
+    } else {
+        Ok(input.split_at(n))
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_rgb<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -117,7 +254,8 @@ fn qoi_op_rgb<'a>(
     Ok(input)
 }
 
-fn qoi_op_rgba<'a>(
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_rgba<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -138,7 +276,8 @@ fn qoi_op_rgba<'a>(
     Ok(input)
 }
 
-fn qoi_op_index<'a>(
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_index<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -155,7 +294,8 @@ fn qoi_op_index<'a>(
     Ok(input)
 }
 
-fn qoi_op_diff<'a>(
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_diff<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -180,7 +320,8 @@ fn qoi_op_diff<'a>(
     Ok(input)
 }
 
-fn qoi_op_luma<'a>(
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_luma<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -209,7 +350,8 @@ fn qoi_op_luma<'a>(
     Ok(input)
 }
 
-fn qoi_op_run<'a>(
+#[cfg(feature = "std")]
+pub(crate) fn qoi_op_run<'a>(
     input: &'a [u8],
     pixels: &mut Vec<u8>,
     state: &mut ParserState,
@@ -228,24 +370,14 @@ fn qoi_op_run<'a>(
     Ok(input)
 }
 
-fn qoi_op_end<'a>(
-    input: &'a [u8],
-    #[allow(clippy::ptr_arg)] _pixels: &mut Vec<u8>,
-    _state: &mut ParserState,
-) -> Result<&'a [u8], ParserError> {
-    let input = tag(&[0u8, 0, 0, 0, 0, 0, 0, 1], input)?;
-
-    Ok(input)
-}
-
 #[derive(Clone, Copy, Debug)]
-struct ParserState {
-    prev: Pixel,
-    seen: [Pixel; 64],
-    is_alpha: bool,
+pub(crate) struct ParserState {
+    pub(crate) prev: Pixel,
+    pub(crate) seen: [Pixel; 64],
+    pub(crate) is_alpha: bool,
 }
 
-fn hash_pixel(pixel: Pixel) -> usize {
+pub(crate) fn hash_pixel(pixel: Pixel) -> usize {
     (pixel.red as usize * 3
         + pixel.green as usize * 5
         + pixel.blue as usize * 7
@@ -253,11 +385,13 @@ fn hash_pixel(pixel: Pixel) -> usize {
         % 64
 }
 
+#[cfg(feature = "std")]
 fn update_state(pixel: Pixel, state: &mut ParserState) {
     state.prev = pixel;
     state.seen[hash_pixel(pixel)] = pixel;
 }
 
+#[cfg(feature = "std")]
 fn push_pixel(pixels: &mut Vec<u8>, pixel: Pixel, is_alpha: bool) {
     pixels.push(pixel.red);
     pixels.push(pixel.green);
@@ -267,19 +401,15 @@ fn push_pixel(pixels: &mut Vec<u8>, pixel: Pixel, is_alpha: bool) {
     }
 }
 
+#[cfg(feature = "std")]
 fn push_pixels(pixels: &mut Vec<u8>, pixel: Pixel, run: usize, is_alpha: bool) {
     if is_alpha {
         for _ in 0..run {
-            pixels.push(pixel.red);
-            pixels.push(pixel.green);
-            pixels.push(pixel.blue);
-            pixels.push(pixel.alpha);
+            pixels.extend_from_slice(&[pixel.red, pixel.green, pixel.blue, pixel.alpha]);
         }
     } else {
         for _ in 0..run {
-            pixels.push(pixel.red);
-            pixels.push(pixel.green);
-            pixels.push(pixel.blue);
+            pixels.extend_from_slice(&[pixel.red, pixel.green, pixel.blue]);
         }
     }
 }
@@ -312,31 +442,79 @@ fn u8(input: &[u8]) -> Result<(u8, &[u8]), ParserError> {
     Ok((num, &input[1..]))
 }
 
-#[derive(Error, Debug, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[derive(Debug, Clone, Copy)]
 pub enum DecoderError {
-    InvalidMagic,
-    InvalidChannels,
-    InvalidColorspace,
-    TooShortHeader,
-    InvalidPixel,
-    TooFewPixels,
-    TooManyPixels,
+    InvalidMagic { offset: usize },
+    InvalidChannels { offset: usize },
+    InvalidColorspace { offset: usize },
+    TooShortHeader { offset: usize },
+    /// The byte at `offset` doesn't match any known op, or the trailing bytes don't match the
+    /// end marker.
+    InvalidPixel { offset: usize, decoded_len: usize },
+    /// The stream ended before the declared dimensions were fully decoded (or before the end
+    /// marker was reached).
+    TooFewPixels { offset: usize, decoded_len: usize },
+    /// More pixels were decoded than the header declares; `pixel_index` is the first one past
+    /// the declared dimensions.
+    TooManyPixels {
+        offset: usize,
+        pixel_index: usize,
+        decoded_len: usize,
+    },
+    BufferTooSmall { required: usize, available: usize },
+}
+
+impl DecoderError {
+    /// Byte offset into the header or content stream where decoding failed, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            DecoderError::InvalidMagic { offset }
+            | DecoderError::InvalidChannels { offset }
+            | DecoderError::InvalidColorspace { offset }
+            | DecoderError::TooShortHeader { offset }
+            | DecoderError::InvalidPixel { offset, .. }
+            | DecoderError::TooFewPixels { offset, .. }
+            | DecoderError::TooManyPixels { offset, .. } => Some(offset),
+            DecoderError::BufferTooSmall { .. } => None,
+        }
+    }
+
+    /// Whether the error leaves no usable output at all (header and buffer-size errors), as
+    /// opposed to a valid, possibly empty, prefix of already-decoded pixels a lenient caller
+    /// could still render.
+    pub fn is_fatal(&self) -> bool {
+        self.usable_prefix_len().is_none()
+    }
+
+    /// Length, in output bytes, of the already-decoded prefix that's still usable despite the
+    /// error, or `None` for header and buffer-size errors. Content-stream errors always return
+    /// `Some`, even `Some(0)` when no pixel was decoded yet.
+    pub fn usable_prefix_len(&self) -> Option<usize> {
+        match *self {
+            DecoderError::InvalidPixel { decoded_len, .. }
+            | DecoderError::TooFewPixels { decoded_len, .. }
+            | DecoderError::TooManyPixels { decoded_len, .. } => Some(decoded_len),
+            _ => None,
+        }
+    }
 }
 
 impl Display for DecoderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{self:?}")
     }
 }
 
-#[derive(Error, Debug, Clone, Copy)]
-enum ParserError {
+#[cfg_attr(feature = "std", derive(Error))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ParserError {
     Recoverable,
     Invalid,
 }
 
 impl Display for ParserError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{self:?}")
     }
 }
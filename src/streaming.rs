@@ -0,0 +1,206 @@
+use crate::parser::{
+    qoi_op_diff, qoi_op_index, qoi_op_luma, qoi_op_rgb, qoi_op_rgba, qoi_op_run, ParserError,
+    ParserState,
+};
+use crate::{parse_image_header, Channels, DecoderError, Header, Pixel};
+
+const QOIF_HEADER_LENGTH: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    Header(Header),
+    Pixels(&'a [u8]),
+    End,
+    Error(DecoderError),
+}
+
+pub struct StreamingDecoder {
+    header: Option<Header>,
+    required: usize,
+    produced: usize,
+    offset: usize,
+    state: ParserState,
+    pending: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+impl StreamingDecoder {
+    pub fn new() -> Self {
+        StreamingDecoder {
+            header: None,
+            required: 0,
+            produced: 0,
+            offset: 0,
+            state: ParserState {
+                prev: Pixel {
+                    red: 0,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                },
+                seen: [Pixel::default(); 64],
+                is_alpha: false,
+            },
+            pending: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn decode_next(&mut self, input: &[u8]) -> (usize, Option<Event<'_>>) {
+        let (consumed, event) = if self.header.is_none() {
+            self.decode_header(input)
+        } else if self.produced >= self.required {
+            self.decode_end(input)
+        } else {
+            self.decode_pixels(input)
+        };
+        self.offset += consumed;
+        (consumed, event)
+    }
+
+    fn decode_header(&mut self, input: &[u8]) -> (usize, Option<Event<'_>>) {
+        let take = (QOIF_HEADER_LENGTH - self.pending.len()).min(input.len());
+        self.pending.extend_from_slice(&input[..take]);
+
+        if self.pending.len() < QOIF_HEADER_LENGTH {
+            return (take, None);
+        }
+
+        let result = parse_image_header(&self.pending);
+        self.pending.clear();
+
+        let header = match result {
+            Ok(header) => header,
+            Err(err) => return (take, Some(Event::Error(err))),
+        };
+
+        let channel_count = match header.channels {
+            Channels::Rgb => 3,
+            Channels::Rgba => 4,
+        };
+        self.required = header.width as usize * header.height as usize * channel_count;
+        self.state.is_alpha = matches!(header.channels, Channels::Rgba);
+        self.header = Some(header);
+
+        (take, Some(Event::Header(header)))
+    }
+
+    fn decode_pixels(&mut self, input: &[u8]) -> (usize, Option<Event<'_>>) {
+        self.scratch.clear();
+        let mut cursor = input;
+        let mut consumed = 0;
+
+        while !self.pending.is_empty() && self.produced < self.required {
+            let Some((&next, rest)) = cursor.split_first() else {
+                return (consumed, None);
+            };
+            self.pending.push(next);
+            cursor = rest;
+            consumed += 1;
+
+            let before = self.scratch.len();
+            match try_decode_op(&self.pending, &mut self.scratch, &mut self.state) {
+                OpOutcome::Decoded(used) => {
+                    self.produced += self.scratch.len() - before;
+                    self.pending.drain(..used);
+                }
+                OpOutcome::NeedMore => {}
+                OpOutcome::Invalid => {
+                    return (
+                        consumed,
+                        Some(Event::Error(DecoderError::InvalidPixel {
+                            offset: self.offset,
+                            decoded_len: self.produced,
+                        })),
+                    )
+                }
+            }
+        }
+
+        while self.produced < self.required && !cursor.is_empty() {
+            let before = self.scratch.len();
+            match try_decode_op(cursor, &mut self.scratch, &mut self.state) {
+                OpOutcome::Decoded(used) => {
+                    cursor = &cursor[used..];
+                    consumed += used;
+                    self.produced += self.scratch.len() - before;
+                }
+                OpOutcome::NeedMore => {
+                    self.pending.extend_from_slice(cursor);
+                    consumed += cursor.len();
+                    break;
+                }
+                OpOutcome::Invalid => {
+                    return (
+                        consumed,
+                        Some(Event::Error(DecoderError::InvalidPixel {
+                            offset: self.offset,
+                            decoded_len: self.produced,
+                        })),
+                    )
+                }
+            }
+        }
+
+        if self.scratch.is_empty() {
+            (consumed, None)
+        } else {
+            (consumed, Some(Event::Pixels(&self.scratch)))
+        }
+    }
+
+    fn decode_end(&mut self, input: &[u8]) -> (usize, Option<Event<'_>>) {
+        let take = (END_MARKER.len() - self.pending.len()).min(input.len());
+        self.pending.extend_from_slice(&input[..take]);
+
+        if self.pending.len() < END_MARKER.len() {
+            return (take, None);
+        }
+
+        let matches_end = self.pending == END_MARKER;
+        self.pending.clear();
+
+        if matches_end {
+            (take, Some(Event::End))
+        } else {
+            (
+                take,
+                Some(Event::Error(DecoderError::InvalidPixel {
+                    offset: self.offset,
+                    decoded_len: self.produced,
+                })),
+            )
+        }
+    }
+}
+
+impl Default for StreamingDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum OpOutcome {
+    Decoded(usize),
+    NeedMore,
+    Invalid,
+}
+
+fn try_decode_op(input: &[u8], pixels: &mut Vec<u8>, state: &mut ParserState) -> OpOutcome {
+    for parser in [
+        qoi_op_rgb,
+        qoi_op_rgba,
+        qoi_op_index,
+        qoi_op_diff,
+        qoi_op_luma,
+        qoi_op_run,
+    ] {
+        match parser(input, pixels, state) {
+            Ok(rest) => return OpOutcome::Decoded(input.len() - rest.len()),
+            Err(ParserError::Recoverable) => {}
+            Err(ParserError::Invalid) => return OpOutcome::NeedMore,
+        }
+    }
+    OpOutcome::Invalid
+}
@@ -1,6 +1,6 @@
 use std::error::Error;
 
-use image::{codecs::qoi::QoiDecoder as ReferenceQoiDecoder, DynamicImage};
+use image::{codecs::qoi::QoiDecoder as ReferenceQoiDecoder, DynamicImage, ImageEncoder};
 
 use super::*;
 
@@ -51,6 +51,145 @@ fn decode_real_image_header() {
     assert_eq!(decoder.color_type(), ColorType::Rgba8);
 }
 
+#[test]
+fn decode_into_matches_parse_image_content() {
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let expected = parse_image_content(&image_bytes[14..], header).unwrap();
+
+    let mut out = vec![0u8; header.required_bytes()];
+    let written = decode_into(&image_bytes[14..], header, &mut out).unwrap();
+
+    assert_eq!(written, expected.len());
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn decode_into_reports_buffer_too_small() {
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+
+    let mut out = vec![0u8; header.required_bytes() - 1];
+    let err = decode_into(&image_bytes[14..], header, &mut out).unwrap_err();
+
+    assert!(matches!(err, DecoderError::BufferTooSmall { .. }));
+}
+
+#[test]
+fn decode_into_reports_error_for_missing_end_marker() {
+    let image_bytes = [
+        b"qoif",
+        &1u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11111110, 1, 2, 3], // QOI_OP_RGB for the only pixel, no end marker follows
+    ]
+    .concat();
+
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let mut out = vec![0u8; header.required_bytes()];
+    let err = decode_into(&image_bytes[14..], header, &mut out).unwrap_err();
+
+    assert!(matches!(err, DecoderError::TooFewPixels { .. }));
+}
+
+#[test]
+fn decode_reports_offset_for_truncated_pixel_data() {
+    let image_bytes = [
+        b"qoif",
+        &2u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11111110, 127, 127], // QOI_OP_RGB missing its blue byte
+    ]
+    .concat();
+
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let err = parse_image_content(&image_bytes[14..], header).unwrap_err();
+
+    assert!(matches!(
+        err,
+        DecoderError::TooFewPixels {
+            offset: 0,
+            decoded_len: 0
+        }
+    ));
+    assert!(!err.is_fatal());
+    assert_eq!(err.usable_prefix_len(), Some(0));
+}
+
+#[test]
+fn decode_into_rejects_overshoot_even_with_slack_buffer() {
+    let image_bytes = [
+        b"qoif",
+        &1u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11000001], // QOI_OP_RUN of 2 pixels, but the header only declares 1
+        &[0, 0, 0, 0, 0, 0, 0, 1],
+    ]
+    .concat();
+
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    // Buffer has room for the overshooting pixel too, but decode_into must still
+    // bound writes by the declared dimensions, not by the buffer's length.
+    let mut out = vec![0u8; header.required_bytes() + 3];
+    let err = decode_into(&image_bytes[14..], header, &mut out).unwrap_err();
+
+    assert!(matches!(
+        err,
+        DecoderError::TooManyPixels {
+            pixel_index: 1,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn decode_reports_pixel_index_when_stream_overshoots_dimensions() {
+    let image_bytes = [
+        b"qoif",
+        &1u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11000001], // QOI_OP_RUN of 2 pixels, but the header only declares 1
+        &[0, 0, 0, 0, 0, 0, 0, 1],
+    ]
+    .concat();
+
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let err = parse_image_content(&image_bytes[14..], header).unwrap_err();
+
+    assert!(matches!(
+        err,
+        DecoderError::TooManyPixels {
+            pixel_index: 1,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn overshoot_usable_prefix_len_stays_within_declared_dimensions() {
+    let image_bytes = [
+        b"qoif",
+        &1u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11000001], // QOI_OP_RUN of 2 pixels, but the header only declares 1
+        &[0, 0, 0, 0, 0, 0, 0, 1],
+    ]
+    .concat();
+
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let err = parse_image_content(&image_bytes[14..], header).unwrap_err();
+
+    // The run would overshoot to 6 decoded bytes; decoding stops after the first 3 (the
+    // 1x1 RGB image's declared pixel), which are within bounds and safe for a lenient
+    // caller to read.
+    assert_eq!(err.usable_prefix_len(), Some(header.required_bytes()));
+}
+
 #[test]
 #[should_panic]
 fn decode_invalid_colorspace() {
@@ -200,6 +339,121 @@ fn decode_wikipedia_008() {
     test_decoding_correctness(image_bytes);
 }
 
+#[test]
+fn encode_roundtrip_dice() {
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    test_encoding_roundtrip(image_bytes);
+}
+
+#[test]
+fn encode_roundtrip_kodim10() {
+    let image_bytes = include_bytes!("../test_images/kodim10.qoi").as_slice();
+    test_encoding_roundtrip(image_bytes);
+}
+
+#[test]
+fn encode_roundtrip_testcard_rgba() {
+    let image_bytes = include_bytes!("../test_images/testcard_rgba.qoi").as_slice();
+    test_encoding_roundtrip(image_bytes);
+}
+
+#[test]
+fn encode_roundtrip_qoi_logo() {
+    let image_bytes = include_bytes!("../test_images/qoi_logo.qoi").as_slice();
+    test_encoding_roundtrip(image_bytes);
+}
+
+fn test_encoding_roundtrip(image_bytes: &[u8]) {
+    let reference_image =
+        reference_decode(image_bytes).expect("There should be no errors in reference implemenation");
+
+    let mut encoded = vec![];
+    QoiEncoder::new(&mut encoded)
+        .write_image(
+            reference_image.as_bytes(),
+            reference_image.width(),
+            reference_image.height(),
+            reference_image.color(),
+        )
+        .unwrap();
+
+    let reencoded_image = reference_decode(&encoded)
+        .expect("There should be no errors decoding our own encoder's output");
+
+    assert!(
+        reencoded_image == reference_image,
+        "Re-encoded image differs from the original"
+    );
+}
+
+#[test]
+fn stream_decode_byte_by_byte_matches_batch_decode() {
+    let image_bytes = [
+        b"qoif",
+        &2u32.to_be_bytes(),
+        &1u32.to_be_bytes(),
+        [3u8, 0].as_slice(),
+        &[0b11111110, 127, 127, 127],
+        &[0b10001010, 0b11110001],
+        &[0, 0, 0, 0, 0, 0, 0, 1],
+    ]
+    .concat();
+
+    test_streaming_decode(&image_bytes, 1);
+}
+
+#[test]
+fn stream_decode_whole_buffer_matches_batch_decode() {
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    test_streaming_decode(image_bytes, image_bytes.len());
+}
+
+#[test]
+fn stream_decode_small_chunks_matches_batch_decode() {
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    test_streaming_decode(image_bytes, 3);
+}
+
+#[test]
+fn stream_decode_sweeps_chunk_sizes_matches_batch_decode() {
+    // dice.qoi mixes QOI_OP_RGB and QOI_OP_LUMA ops, so this sweep exercises
+    // partial-op straddles across a range of chunk boundaries, not just chunk
+    // size 3.
+    let image_bytes = include_bytes!("../test_images/dice.qoi").as_slice();
+    for chunk_size in 2..=6 {
+        test_streaming_decode(image_bytes, chunk_size);
+    }
+}
+
+fn test_streaming_decode(image_bytes: &[u8], chunk_size: usize) {
+    let header = parse_image_header(&image_bytes[..14]).unwrap();
+    let expected_pixels = parse_image_content(&image_bytes[14..], header).unwrap();
+
+    let mut decoder = StreamingDecoder::new();
+    let mut decoded_header = None;
+    let mut decoded_pixels = vec![];
+    let mut ended = false;
+
+    for chunk in image_bytes.chunks(chunk_size) {
+        let mut remaining = chunk;
+        while !remaining.is_empty() {
+            let (consumed, event) = decoder.decode_next(remaining);
+            match event {
+                Some(Event::Header(header)) => decoded_header = Some(header),
+                Some(Event::Pixels(pixels)) => decoded_pixels.extend_from_slice(pixels),
+                Some(Event::End) => ended = true,
+                Some(Event::Error(err)) => panic!("unexpected decoding error: {err}"),
+                None => {}
+            }
+            remaining = &remaining[consumed..];
+        }
+    }
+
+    assert_eq!(decoded_header, Some(header));
+    assert_eq!(decoded_pixels, expected_pixels);
+    assert!(ended, "stream should have reached the end marker");
+}
+
 fn test_decoding_correctness(image_bytes: &[u8]) {
     let reference_image = reference_decode(image_bytes)
         .expect("There should be no errors in reference implemenation");